@@ -0,0 +1,117 @@
+//! Groups timestamped results into fixed-size time buckets and keeps only
+//! the nearest approach per bucket, so repeated visits to a spot show up as
+//! one row each instead of drowning each other out in a global sort.
+
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Parses a `--bin` duration like `"1d"`, `"6h"`, `"30m"`, or `"45s"`.
+pub fn parse_bin_duration(input: &str) -> Result<chrono::Duration, String> {
+    let input = input.trim();
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit (d/h/m/s) in --bin `{}`", input))?;
+    let (amount_str, unit) = input.split_at(split_at);
+
+    let amount = amount_str
+        .parse::<i64>()
+        .map_err(|_| format!("invalid amount in --bin `{}`", input))?;
+
+    match unit {
+        "d" => Ok(chrono::Duration::days(amount)),
+        "h" => Ok(chrono::Duration::hours(amount)),
+        "m" => Ok(chrono::Duration::minutes(amount)),
+        "s" => Ok(chrono::Duration::seconds(amount)),
+        _ => Err(format!("unknown --bin unit `{}` (expected d/h/m/s)", unit)),
+    }
+}
+
+/// Buckets `(time, distance)` pairs by `bin` and returns the bucket start
+/// time paired with the index of the closest-approach entry in each bucket,
+/// ordered by bucket start.
+pub fn bucket_nearest<T>(
+    entries: &[T],
+    bin: chrono::Duration,
+    time_of: impl Fn(&T) -> Option<DateTime<Utc>>,
+    distance_of: impl Fn(&T) -> f64,
+) -> Vec<(DateTime<Utc>, usize)> {
+    let bin_millis = bin.num_milliseconds().max(1);
+    let mut buckets: std::collections::BTreeMap<i64, usize> = std::collections::BTreeMap::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let time = match time_of(entry) {
+            Some(time) => time,
+            None => continue,
+        };
+        let bucket_key = time.timestamp_millis().div_euclid(bin_millis);
+
+        buckets
+            .entry(bucket_key)
+            .and_modify(|best| {
+                if distance_of(&entries[*best]) > distance_of(entry) {
+                    *best = index;
+                }
+            })
+            .or_insert(index);
+    }
+
+    buckets
+        .into_iter()
+        .map(|(bucket_key, index)| {
+            (
+                Utc.timestamp_millis(bucket_key * bin_millis),
+                index,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_supported_unit() {
+        assert_eq!(parse_bin_duration("1d").unwrap(), chrono::Duration::days(1));
+        assert_eq!(parse_bin_duration("6h").unwrap(), chrono::Duration::hours(6));
+        assert_eq!(parse_bin_duration("30m").unwrap(), chrono::Duration::minutes(30));
+        assert_eq!(parse_bin_duration("45s").unwrap(), chrono::Duration::seconds(45));
+    }
+
+    #[test]
+    fn rejects_missing_unit() {
+        assert!(parse_bin_duration("30").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_unit() {
+        assert!(parse_bin_duration("30w").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_amount() {
+        assert!(parse_bin_duration("xh").is_err());
+    }
+
+    #[test]
+    fn buckets_and_keeps_nearest_per_bucket() {
+        let entries = vec![
+            (Some(Utc.timestamp(0, 0)), 50.0),
+            (Some(Utc.timestamp(10, 0)), 5.0),
+            (Some(Utc.timestamp(3600, 0)), 20.0),
+            (None, 1.0),
+        ];
+
+        let buckets = bucket_nearest(
+            &entries,
+            chrono::Duration::hours(1),
+            |entry| entry.0,
+            |entry| entry.1,
+        );
+
+        assert_eq!(buckets.len(), 2);
+        // First bucket (hour 0) should keep index 1 (distance 5.0), not index 0.
+        assert_eq!(buckets[0].1, 1);
+        // Second bucket (hour 1) should keep index 2, the only entry in it.
+        assert_eq!(buckets[1].1, 2);
+    }
+}
@@ -0,0 +1,141 @@
+//! A validated geographic coordinate, parsed from any of the formats this
+//! tool has historically accepted: decimal degrees (`"48.2 16.3"`),
+//! comma-decimal (`"48,2 16,3"`), and degrees-decimal-minutes with an N/S/E/W
+//! prefix (`"N48 12.0E16 18.0"`).
+
+use std::{error::Error, fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinate {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseCoordError {
+    BadLat(f64),
+    BadLon(f64),
+    Malformed(String),
+}
+
+impl fmt::Display for ParseCoordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseCoordError::BadLat(lat) => {
+                write!(f, "latitude `{}` is out of range (-90..=90)", lat)
+            }
+            ParseCoordError::BadLon(lon) => {
+                write!(f, "longitude `{}` is out of range (-180..=180)", lon)
+            }
+            ParseCoordError::Malformed(input) => {
+                write!(f, "could not parse a coordinate from `{}`", input)
+            }
+        }
+    }
+}
+
+impl Error for ParseCoordError {}
+
+impl FromStr for Coordinate {
+    type Err = ParseCoordError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let trimmed = input.trim();
+
+        let (lat, lon) = parse_decimal(trimmed)
+            .or_else(|| parse_degrees_minutes(trimmed))
+            .ok_or_else(|| ParseCoordError::Malformed(trimmed.to_string()))?;
+
+        if !(-90.0..=90.0).contains(&lat) {
+            return Err(ParseCoordError::BadLat(lat));
+        }
+        if !(-180.0..=180.0).contains(&lon) {
+            return Err(ParseCoordError::BadLon(lon));
+        }
+
+        Ok(Coordinate { lat, lon })
+    }
+}
+
+fn parse_decimal(input: &str) -> Option<(f64, f64)> {
+    let (lat_str, lon_str) = input.split_once(' ')?;
+    // Comma-decimal input uses `,` as the decimal point, not a thousands
+    // separator, so swap it for `.` rather than deleting it.
+    let lat = lat_str.replace(',', ".").parse::<f64>().ok()?;
+    let lon = lon_str.replace(',', ".").parse::<f64>().ok()?;
+    Some((lat, lon))
+}
+
+fn parse_degrees_minutes(input: &str) -> Option<(f64, f64)> {
+    let (first, second) = input.split_at(input.len() / 2);
+    let lat = parse_deg_min_sec(first)?;
+    let lon = parse_deg_min_sec(second)?;
+    Some((lat, lon))
+}
+
+fn parse_deg_min_sec(input: &str) -> Option<f64> {
+    let input = input.trim();
+    let mut chars = input.chars();
+    let first = chars.next()?;
+    let south_west = first.eq(&'S') || first.eq(&'W');
+    let rest = chars.as_str().trim();
+
+    let mut parts = rest.split(' ');
+    let degrees = parts.next()?.replace('°', "").parse::<f64>().ok()?;
+    let minutes = parts.next()?.parse::<f64>().ok()?;
+
+    let mut value = degrees + minutes / 60.0;
+    if south_west {
+        value *= -1.0;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_decimal_degrees() {
+        let coord = "48.2 16.3".parse::<Coordinate>().unwrap();
+        assert_eq!(coord, Coordinate { lat: 48.2, lon: 16.3 });
+    }
+
+    #[test]
+    fn parses_comma_decimal_degrees() {
+        let coord = "48,2 16,3".parse::<Coordinate>().unwrap();
+        assert_eq!(coord, Coordinate { lat: 48.2, lon: 16.3 });
+    }
+
+    #[test]
+    fn parses_degrees_decimal_minutes_with_prefix() {
+        let coord = "N48 12.0E16 18.0".parse::<Coordinate>().unwrap();
+        assert!((coord.lat - (48.0 + 12.0 / 60.0)).abs() < 1e-9);
+        assert!((coord.lon - (16.0 + 18.0 / 60.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parses_degrees_decimal_minutes_with_south_west_prefix() {
+        let coord = "S48 12.0W16 18.0".parse::<Coordinate>().unwrap();
+        assert!((coord.lat - -(48.0 + 12.0 / 60.0)).abs() < 1e-9);
+        assert!((coord.lon - -(16.0 + 18.0 / 60.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_out_of_range_latitude() {
+        let err = "91.0 16.3".parse::<Coordinate>().unwrap_err();
+        assert_eq!(err, ParseCoordError::BadLat(91.0));
+    }
+
+    #[test]
+    fn rejects_out_of_range_longitude() {
+        let err = "48.2 181.0".parse::<Coordinate>().unwrap_err();
+        assert_eq!(err, ParseCoordError::BadLon(181.0));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let err = "not a coordinate".parse::<Coordinate>().unwrap_err();
+        assert_eq!(err, ParseCoordError::Malformed("not a coordinate".to_string()));
+    }
+}
@@ -0,0 +1,177 @@
+//! Structured output formats for search results, in addition to the
+//! historical `;`-delimited text printed by [`crate::print_result`].
+
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::GpxResult;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Csv,
+    Json,
+    Geojson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "text" => Ok(OutputFormat::Text),
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "geojson" => Ok(OutputFormat::Geojson),
+            _ => Err(format!(
+                "unknown --format `{}` (expected text, csv, json, or geojson)",
+                input
+            )),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    distance: f64,
+    lat: f64,
+    lon: f64,
+    time: Option<&'a str>,
+    path: &'a str,
+}
+
+#[derive(Serialize)]
+struct GeoJsonGeometry {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    coordinates: [f64; 2],
+}
+
+#[derive(Serialize)]
+struct GeoJsonProperties<'a> {
+    distance: f64,
+    time: Option<&'a str>,
+    path: &'a str,
+}
+
+#[derive(Serialize)]
+struct GeoJsonFeature<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    geometry: GeoJsonGeometry,
+    properties: GeoJsonProperties<'a>,
+}
+
+#[derive(Serialize)]
+struct GeoJsonFeatureCollection<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<GeoJsonFeature<'a>>,
+}
+
+/// Prints `results` in `format`. Callers handle `OutputFormat::Text`
+/// themselves via [`crate::print_result`] to preserve its existing
+/// "nearest out of distance" fallback; this only needs to cover the
+/// structured formats.
+pub fn print_results(format: OutputFormat, results: &[&GpxResult]) {
+    match format {
+        OutputFormat::Text => results.iter().copied().for_each(crate::print_result),
+        OutputFormat::Csv => print_csv(results),
+        OutputFormat::Json => print_json(results),
+        OutputFormat::Geojson => print_geojson(results),
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains(',') || value.contains('"') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn print_csv(results: &[&GpxResult]) {
+    println!("distance,lat,lon,time,path");
+    for result in results {
+        println!(
+            "{},{},{},{},{}",
+            result.distance,
+            result.lat,
+            result.lon,
+            result.time.as_deref().unwrap_or(""),
+            csv_escape(&result.path)
+        );
+    }
+}
+
+fn print_json(results: &[&GpxResult]) {
+    let records = results
+        .iter()
+        .map(|result| JsonRecord {
+            distance: result.distance,
+            lat: result.lat,
+            lon: result.lon,
+            time: result.time.as_deref(),
+            path: &result.path,
+        })
+        .collect::<Vec<_>>();
+
+    match serde_json::to_string_pretty(&records) {
+        Ok(json) => println!("{}", json),
+        Err(err) => eprintln!("[Error] Could not serialize results to JSON: {}", err),
+    }
+}
+
+fn print_geojson(results: &[&GpxResult]) {
+    let features = results
+        .iter()
+        .map(|result| GeoJsonFeature {
+            kind: "Feature",
+            geometry: GeoJsonGeometry {
+                kind: "Point",
+                coordinates: [result.lon, result.lat],
+            },
+            properties: GeoJsonProperties {
+                distance: result.distance,
+                time: result.time.as_deref(),
+                path: &result.path,
+            },
+        })
+        .collect();
+
+    let collection = GeoJsonFeatureCollection {
+        kind: "FeatureCollection",
+        features,
+    };
+
+    match serde_json::to_string_pretty(&collection) {
+        Ok(json) => println!("{}", json),
+        Err(err) => eprintln!("[Error] Could not serialize results to GeoJSON: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str_accepts_known_formats() {
+        assert_eq!("text".parse::<OutputFormat>().unwrap(), OutputFormat::Text);
+        assert_eq!("csv".parse::<OutputFormat>().unwrap(), OutputFormat::Csv);
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!("geojson".parse::<OutputFormat>().unwrap(), OutputFormat::Geojson);
+    }
+
+    #[test]
+    fn from_str_rejects_unknown_format() {
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn csv_escape_quotes_values_containing_commas_or_quotes() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("a\"b"), "\"a\"\"b\"");
+    }
+}
@@ -0,0 +1,88 @@
+//! Geodesic distance calculations on the WGS-84 mean sphere.
+//!
+//! Replaces the old flat-earth (equirectangular) projection, which broke down
+//! at high latitudes and over long segments, with the Haversine formula for
+//! point-to-point distance and the great-circle cross-track formula for
+//! point-to-segment distance.
+
+/// Mean earth radius in meters, as used by the Haversine formula.
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// Great-circle distance between two points given in radians, in meters.
+pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = lat2 - lat1;
+    let d_lon = lon2 - lon1;
+
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+
+    EARTH_RADIUS_METERS * c
+}
+
+/// Initial bearing (radians) of the great circle from point 1 to point 2.
+fn bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lon = lon2 - lon1;
+
+    (d_lon.sin() * lat2.cos()).atan2(lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos())
+}
+
+/// Distance in meters from the point `(lat3, lon3)` to the great-circle
+/// segment running from `(lat1, lon1)` to `(lat2, lon2)`, all given in
+/// radians.
+///
+/// Uses the cross-track distance formula, clamping to the nearest segment
+/// endpoint when the along-track distance falls outside the segment.
+pub fn distance_to_segment(
+    lat1: f64,
+    lon1: f64,
+    lat2: f64,
+    lon2: f64,
+    lat3: f64,
+    lon3: f64,
+) -> f64 {
+    let angular_dist_13 = haversine_distance(lat1, lon1, lat3, lon3) / EARTH_RADIUS_METERS;
+    let bearing_13 = bearing(lat1, lon1, lat3, lon3);
+    let bearing_12 = bearing(lat1, lon1, lat2, lon2);
+
+    let cross_track = (angular_dist_13.sin() * (bearing_13 - bearing_12).sin()).asin()
+        * EARTH_RADIUS_METERS;
+
+    // `acos` only ever returns a value in `[0, π]`, so it can never signal
+    // "behind the segment start" — use the signed atan2 form instead, which
+    // is negative when point 3 projects behind point 1.
+    let along_track = (angular_dist_13.sin() * (bearing_13 - bearing_12).cos())
+        .atan2(angular_dist_13.cos())
+        * EARTH_RADIUS_METERS;
+
+    let segment_len = haversine_distance(lat1, lon1, lat2, lon2);
+
+    if along_track < 0.0 {
+        haversine_distance(lat1, lon1, lat3, lon3)
+    } else if along_track > segment_len {
+        haversine_distance(lat2, lon2, lat3, lon3)
+    } else {
+        cross_track.abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_track_clamps_to_segment_start_when_query_is_behind_it() {
+        let (lat1, lon1) = (0.0f64.to_radians(), 0.0f64.to_radians());
+        let (lat2, lon2) = (0.0f64.to_radians(), 1.0f64.to_radians());
+        let (lat3, lon3) = (0.0f64.to_radians(), (-0.5f64).to_radians());
+
+        let distance = distance_to_segment(lat1, lon1, lat2, lon2, lat3, lon3);
+        let expected = haversine_distance(lat1, lon1, lat3, lon3);
+
+        assert!(
+            (distance - expected).abs() < 1.0,
+            "expected ~{}m (clamped to segment start), got {}m",
+            expected,
+            distance
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Geotags photos that have no GPS EXIF data by correlating their capture
+//! time (EXIF `DateTimeOriginal`) against the timestamps already parsed out
+//! of the GPX track set, interpolating position between the two bracketing
+//! track points.
+
+use std::{fs, io::BufReader, path::Path, path::PathBuf};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use exif::{In, Tag};
+
+use crate::index::SpatialIndex;
+
+/// A track point reduced to just what geotagging needs.
+pub struct TimedPoint {
+    pub time: DateTime<Utc>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Collects every indexed point that has a parseable time, sorted
+/// chronologically so capture times can be bracketed with a binary search.
+pub fn sorted_points(index: &SpatialIndex) -> Vec<TimedPoint> {
+    let mut points = index
+        .tree
+        .iter()
+        .filter_map(|point| {
+            let time = point.time.as_ref()?.parse::<DateTime<Utc>>().ok()?;
+            Some(TimedPoint {
+                time,
+                lat: point.lat,
+                lon: point.lon,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    points.sort_by_key(|point| point.time);
+    points
+}
+
+/// Geotags every JPEG in `image_dir`, printing `lat;lon;path` for each photo
+/// it could place, and writing GPS EXIF tags back when `write_exif` is set.
+pub fn run(image_dir: &Path, points: &[TimedPoint], write_exif: bool) {
+    for image_path in collect_images(image_dir) {
+        let capture_time = match read_capture_time(&image_path) {
+            Some(time) => time,
+            None => {
+                eprintln!(
+                    "[WARNING] No EXIF capture time in: {}",
+                    image_path.to_str().unwrap()
+                );
+                continue;
+            }
+        };
+
+        match interpolate_position(points, capture_time) {
+            Some((lat, lon)) => {
+                println!("{:.6};{:.6};{}", lat, lon, image_path.to_str().unwrap());
+
+                if write_exif {
+                    if let Err(err) = write_gps_exif(&image_path, lat, lon) {
+                        eprintln!(
+                            "[WARNING] Could not write EXIF GPS tags to {}: {}",
+                            image_path.to_str().unwrap(),
+                            err
+                        );
+                    }
+                }
+            }
+            None => eprintln!(
+                "[WARNING] No track points near the capture time to geotag: {}",
+                image_path.to_str().unwrap()
+            ),
+        }
+    }
+}
+
+fn collect_images(dir: &Path) -> Vec<PathBuf> {
+    let mut images = Vec::new();
+
+    if let Ok(dir_entries) = fs::read_dir(dir) {
+        for dir_entry in dir_entries.filter_map(Result::ok) {
+            if let Some(ext) = dir_entry.path().extension().and_then(|ext| ext.to_str()) {
+                if ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg") {
+                    images.push(dir_entry.path());
+                }
+            }
+        }
+    }
+
+    images
+}
+
+fn read_capture_time(path: &Path) -> Option<DateTime<Utc>> {
+    let file = fs::File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY)?;
+    let raw = field.display_value().to_string();
+
+    let naive = NaiveDateTime::parse_from_str(&raw, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(&raw, "%Y:%m:%d %H:%M:%S"))
+        .ok()?;
+
+    Some(DateTime::<Utc>::from_utc(naive, Utc))
+}
+
+/// Beyond this gap between a photo's capture time and the track point(s)
+/// used to place it, the points are almost certainly from an unrelated trip
+/// rather than the same outing, so we'd rather refuse to place the photo
+/// than silently blend in a bogus position.
+fn max_interpolation_gap() -> chrono::Duration {
+    chrono::Duration::hours(2)
+}
+
+/// Interpolates a position at `target` between the two track points
+/// bracketing it in time, clamping to the nearest endpoint if `target` falls
+/// outside the track's time range entirely. Returns `None` if `target` isn't
+/// within [`max_interpolation_gap()`] of the track points that would be used,
+/// rather than guessing a position from an unrelated trip.
+fn interpolate_position(points: &[TimedPoint], target: DateTime<Utc>) -> Option<(f64, f64)> {
+    if points.is_empty() {
+        return None;
+    }
+
+    let after_index = points.partition_point(|point| point.time < target);
+
+    if after_index == 0 {
+        let first = &points[0];
+        return if first.time - target <= max_interpolation_gap() {
+            Some((first.lat, first.lon))
+        } else {
+            None
+        };
+    }
+    if after_index == points.len() {
+        let last = &points[points.len() - 1];
+        return if target - last.time <= max_interpolation_gap() {
+            Some((last.lat, last.lon))
+        } else {
+            None
+        };
+    }
+
+    let before = &points[after_index - 1];
+    let after = &points[after_index];
+
+    if target - before.time > max_interpolation_gap() || after.time - target > max_interpolation_gap() {
+        return None;
+    }
+
+    let span = (after.time - before.time).num_milliseconds() as f64;
+    let fraction = if span > 0.0 {
+        (target - before.time).num_milliseconds() as f64 / span
+    } else {
+        0.0
+    };
+
+    Some((
+        before.lat + (after.lat - before.lat) * fraction,
+        before.lon + (after.lon - before.lon) * fraction,
+    ))
+}
+
+fn write_gps_exif(path: &Path, lat: f64, lon: f64) -> Result<(), String> {
+    use little_exif::exif_tag::ExifTag;
+    use little_exif::metadata::Metadata;
+
+    let mut metadata = Metadata::new_from_path(path).map_err(|err| err.to_string())?;
+
+    metadata.set_tag(ExifTag::GPSLatitudeRef(if lat.is_sign_negative() {
+        "S".to_string()
+    } else {
+        "N".to_string()
+    }));
+    metadata.set_tag(ExifTag::GPSLatitude(decimal_to_dms(lat.abs())));
+
+    metadata.set_tag(ExifTag::GPSLongitudeRef(if lon.is_sign_negative() {
+        "W".to_string()
+    } else {
+        "E".to_string()
+    }));
+    metadata.set_tag(ExifTag::GPSLongitude(decimal_to_dms(lon.abs())));
+
+    metadata.write_to_file(path).map_err(|err| err.to_string())
+}
+
+/// Converts an unsigned decimal-degree magnitude into EXIF's
+/// degrees/minutes/seconds rational triple.
+fn decimal_to_dms(value: f64) -> Vec<little_exif::rational::uR64> {
+    let degrees = value.trunc();
+    let minutes = (value - degrees) * 60.0;
+    let seconds = (minutes - minutes.trunc()) * 60.0;
+
+    vec![
+        little_exif::rational::uR64 {
+            nominator: degrees as u32,
+            denominator: 1,
+        },
+        little_exif::rational::uR64 {
+            nominator: minutes.trunc() as u32,
+            denominator: 1,
+        },
+        little_exif::rational::uR64 {
+            nominator: (seconds * 1000.0).round() as u32,
+            denominator: 1000,
+        },
+    ]
+}
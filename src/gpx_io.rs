@@ -0,0 +1,30 @@
+//! Shared GPX file opening, transparently decompressing `.gpx.gz` archives.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+
+/// Opens `path` as an XML reader, transparently gunzipping it first if its
+/// name ends in `.gz`.
+pub fn open_xml_reader(path: &Path) -> io::Result<quick_xml::Reader<Box<dyn BufRead>>> {
+    let file = File::open(path)?;
+
+    let is_gzipped = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| name.ends_with(".gz"));
+
+    let reader: Box<dyn BufRead> = if is_gzipped {
+        Box::new(BufReader::new(GzDecoder::new(BufReader::new(file))))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+
+    let mut xml_reader = quick_xml::Reader::from_reader(reader);
+    xml_reader.trim_text(true);
+    Ok(xml_reader)
+}
@@ -0,0 +1,243 @@
+//! Spatial index over every `trkpt` found under the search root.
+//!
+//! Parsing and scanning every GPX file on each invocation is `O(total
+//! points)` per query. Instead we parse everything once, collect every track
+//! point into an [`rstar::RTree`], and cache the tree to disk (stamped with
+//! each source file's mtime) so later runs can answer "points within
+//! distance" with [`RTree::locate_within_distance`] instead of rescanning.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use rstar::{PointDistance, RTree, RTreeObject, AABB};
+use serde::{Deserialize, Serialize};
+
+use crate::geo;
+use crate::gpx_io;
+
+const INDEX_FILE_NAME: &str = ".gpx-analyzer-index.bin";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedPoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub path: PathBuf,
+    pub time: Option<String>,
+}
+
+impl RTreeObject for IndexedPoint {
+    type Envelope = AABB<[f64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_point([self.lon, self.lat])
+    }
+}
+
+impl PointDistance for IndexedPoint {
+    fn distance_2(&self, point: &[f64; 2]) -> f64 {
+        let d_lon = self.lon - point[0];
+        let d_lat = self.lat - point[1];
+        d_lon * d_lon + d_lat * d_lat
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpatialIndex {
+    pub tree: RTree<IndexedPoint>,
+    file_mtimes: HashMap<PathBuf, SystemTime>,
+}
+
+impl SpatialIndex {
+    /// Parses every file in `gpx_files` and builds a fresh tree.
+    pub fn build(gpx_files: &[PathBuf]) -> Self {
+        let mut points = Vec::new();
+        let mut file_mtimes = HashMap::new();
+
+        for file in gpx_files {
+            if let Ok(mtime) = fs::metadata(file).and_then(|metadata| metadata.modified()) {
+                file_mtimes.insert(file.clone(), mtime);
+            }
+            points.extend(collect_points(file));
+        }
+
+        SpatialIndex {
+            tree: RTree::bulk_load(points),
+            file_mtimes,
+        }
+    }
+
+    /// True if any indexed file is missing an mtime entry, was removed, or
+    /// was modified since the index was built.
+    pub fn is_stale(&self, gpx_files: &[PathBuf]) -> bool {
+        if self.file_mtimes.len() != gpx_files.len() {
+            return true;
+        }
+
+        gpx_files.iter().any(|file| {
+            let current_mtime = fs::metadata(file).and_then(|metadata| metadata.modified()).ok();
+            self.file_mtimes.get(file).copied() != current_mtime
+        })
+    }
+
+    /// Points within `distance_meters` of `(lat, lon)` (degrees), using the
+    /// tree (or, near a pole, a full-longitude bounding box) to prune
+    /// candidates and an exact Haversine check to confirm them.
+    pub fn points_within_distance(
+        &self,
+        lat: f64,
+        lon: f64,
+        distance_meters: f64,
+    ) -> Vec<&IndexedPoint> {
+        // Above this latitude, a single "inflate the circular radius by
+        // 1/cos(lat)" correction isn't safe: it only accounts for the
+        // *query's* latitude, while a matching candidate point nearer the
+        // pole shrinks its own degrees-of-longitude even further and can
+        // still be pruned. Fall back to an exact full-longitude band query
+        // instead of guessing a radius.
+        const HIGH_LATITUDE_THRESHOLD_DEGREES: f64 = 80.0;
+
+        let meters_per_degree = (2.0 * std::f64::consts::PI * geo::EARTH_RADIUS_METERS) / 360.0;
+        // Pad the tree query radius generously; the exact Haversine filter
+        // below throws out the slop.
+        let distance_degrees = (distance_meters / meters_per_degree) * 1.5;
+
+        let lat_rad = lat.to_radians();
+        let lon_rad = lon.to_radians();
+
+        let candidates: Vec<&IndexedPoint> =
+            if lat.abs() + distance_degrees >= HIGH_LATITUDE_THRESHOLD_DEGREES {
+                let lat_min = (lat - distance_degrees).max(-90.0);
+                let lat_max = (lat + distance_degrees).min(90.0);
+                self.points_in_bbox(lat_min, -180.0, lat_max, 180.0)
+            } else {
+                // A degree of longitude shrinks by cos(lat), so widen the
+                // circular query radius to still cover the east-west extent.
+                let radius_degrees = distance_degrees / lat_rad.cos();
+                self.tree
+                    .locate_within_distance([lon, lat], radius_degrees * radius_degrees)
+                    .collect()
+            };
+
+        candidates
+            .into_iter()
+            .filter(|point| {
+                geo::haversine_distance(lat_rad, lon_rad, point.lat.to_radians(), point.lon.to_radians())
+                    <= distance_meters
+            })
+            .collect()
+    }
+
+    /// Every indexed point inside the axis-aligned rectangle
+    /// `(lat_min, lon_min)`..`(lat_max, lon_max)`.
+    pub fn points_in_bbox(
+        &self,
+        lat_min: f64,
+        lon_min: f64,
+        lat_max: f64,
+        lon_max: f64,
+    ) -> Vec<&IndexedPoint> {
+        let envelope = AABB::from_corners([lon_min, lat_min], [lon_max, lat_max]);
+
+        self.tree
+            .locate_in_envelope_intersecting(&envelope)
+            .collect()
+    }
+}
+
+fn collect_points(path: &Path) -> Vec<IndexedPoint> {
+    let mut points = Vec::new();
+
+    let mut reader = match gpx_io::open_xml_reader(path) {
+        Ok(reader) => reader,
+        Err(_) => return points,
+    };
+    let mut buf = Vec::new();
+
+    let mut current: Option<(f64, f64)> = None;
+    let mut in_time = false;
+    let mut current_time = None;
+
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(quick_xml::events::Event::Start(ref e)) => {
+                if e.name().eq(b"trkpt") {
+                    let lon = e
+                        .attributes()
+                        .find(|attr| attr.as_ref().map_or(false, |attr| attr.key == b"lon"))
+                        .and_then(Result::ok)
+                        .and_then(|attr| attr.unescape_and_decode_value(&reader).ok())
+                        .and_then(|value| value.parse::<f64>().ok());
+                    let lat = e
+                        .attributes()
+                        .find(|attr| attr.as_ref().map_or(false, |attr| attr.key == b"lat"))
+                        .and_then(Result::ok)
+                        .and_then(|attr| attr.unescape_and_decode_value(&reader).ok())
+                        .and_then(|value| value.parse::<f64>().ok());
+
+                    current = lon.zip(lat).map(|(lon, lat)| (lat, lon));
+                    current_time = None;
+                } else if e.name().eq(b"time") {
+                    in_time = true;
+                }
+            }
+            Ok(quick_xml::events::Event::Text(e)) => {
+                if in_time {
+                    if let Ok(time) = e.unescape_and_decode(&reader) {
+                        if !time.is_empty() {
+                            current_time = Some(time);
+                        }
+                    }
+                }
+            }
+            Ok(quick_xml::events::Event::End(e)) => {
+                if e.name().eq(b"time") {
+                    in_time = false;
+                } else if e.name().eq(b"trkpt") {
+                    if let Some((lat, lon)) = current.take() {
+                        points.push(IndexedPoint {
+                            lat,
+                            lon,
+                            path: path.to_path_buf(),
+                            time: current_time.take(),
+                        });
+                    }
+                }
+            }
+            Ok(quick_xml::events::Event::Eof) => break,
+            Err(_) => break,
+            _ => (),
+        }
+    }
+
+    points
+}
+
+fn cache_path(search_root: &Path) -> PathBuf {
+    search_root.join(INDEX_FILE_NAME)
+}
+
+/// Loads a fresh index from the on-disk cache next to `search_root`, or
+/// builds (and caches) one if it is missing, stale, or `force_rebuild` is set.
+pub fn load_or_build(search_root: &Path, gpx_files: &[PathBuf], force_rebuild: bool) -> SpatialIndex {
+    let cache_path = cache_path(search_root);
+
+    if !force_rebuild {
+        if let Ok(bytes) = fs::read(&cache_path) {
+            if let Ok(index) = bincode::deserialize::<SpatialIndex>(&bytes) {
+                if !index.is_stale(gpx_files) {
+                    return index;
+                }
+            }
+        }
+    }
+
+    let index = SpatialIndex::build(gpx_files);
+    if let Ok(bytes) = bincode::serialize(&index) {
+        let _ = fs::write(&cache_path, bytes);
+    }
+    index
+}
@@ -7,45 +7,89 @@ use chrono::prelude::*;
 use clap::Clap;
 use rayon::prelude::*;
 
+mod binning;
+mod coordinate;
+mod format;
+mod geo;
+mod geotag;
+mod gpx_io;
+mod index;
+
 #[derive(Debug, Clap)]
 #[clap(name = "gpx-analyzer")]
 struct Opt {
     #[clap(short, long, allow_hyphen_values = true)]
-    pub coordinate: String,
+    pub coordinate: Option<String>,
     #[clap(short, long)]
-    pub distance: f64,
+    pub distance: Option<f64>,
+    /// Axis-aligned geographic rectangle "lat_min,lon_min lat_max,lon_max";
+    /// returns every track point inside it instead of a radius search.
+    #[clap(long, allow_hyphen_values = true)]
+    pub bbox: Option<String>,
+    /// Geotag every JPEG in this directory by EXIF capture time instead of
+    /// running a radius/bbox search.
+    #[clap(long)]
+    pub geotag: Option<PathBuf>,
+    /// With --geotag, also write the inferred GPSLatitude/GPSLongitude EXIF
+    /// tags back into each photo.
+    #[clap(long)]
+    pub write_exif: bool,
+    /// Skip track points timestamped before this RFC3339 instant.
+    #[clap(long)]
+    pub after: Option<String>,
+    /// Skip track points timestamped after this RFC3339 instant.
+    #[clap(long)]
+    pub before: Option<String>,
+    /// Group matching points into buckets of this duration (e.g. "1d", "6h")
+    /// and report only the nearest approach per bucket.
+    #[clap(long)]
+    pub bin: Option<String>,
+    /// Output format for results: text, csv, json, or geojson.
+    #[clap(long, default_value = "text")]
+    pub format: String,
     #[clap(short = 'j', long)]
     pub threads: Option<usize>,
     #[clap(short, long)]
     pub recursive: bool,
+    #[clap(long)]
+    pub reindex: bool,
     #[clap(name = "PATH")]
     pub path: Option<PathBuf>,
 }
 
 #[derive(Default)]
-struct GpxResult {
-    distance: f64,
-    path: String,
-    time: Option<String>,
+pub(crate) struct GpxResult {
+    pub(crate) distance: f64,
+    pub(crate) path: String,
+    pub(crate) time: Option<String>,
+    pub(crate) lat: f64,
+    pub(crate) lon: f64,
 }
 
 fn analyze(
     path: &Path,
     lat: f64,
     lon: f64,
-    deg_lat_to_dist: f64,
-    deg_lon_to_dist: f64,
     distance: f64,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
 ) -> Vec<GpxResult> {
-    let mut reader = quick_xml::Reader::from_file(&path).unwrap();
-    reader.trim_text(true);
+    let lat = lat.to_radians();
+    let lon = lon.to_radians();
+
+    let mut reader = gpx_io::open_xml_reader(path).unwrap();
     let mut buf = Vec::new();
 
     let mut results: Vec<GpxResult> = Vec::new();
     let mut new_results: Vec<GpxResult> = Vec::new();
     let mut nearest: Option<GpxResult> = None;
+    // Holds whatever `nearest` pointed at right before the in-flight
+    // candidate overwrote it, so a rejected candidate (time outside the
+    // window, or no time at all) can be undone without losing it.
+    let mut previous_nearest: Option<GpxResult> = None;
     let mut time_update = false;
     let mut in_time = false;
+    let mut saw_time_this_point = false;
     let mut searching_time_for = std::usize::MAX;
     let mut last_point = None;
 
@@ -53,6 +97,8 @@ fn analyze(
         match reader.read_event(&mut buf) {
             Ok(quick_xml::events::Event::Start(ref e)) => {
                 if e.name().eq(b"trkpt") {
+                    saw_time_this_point = false;
+
                     let (found_lon, found_lat) = (
                         if let Some(attr) = e
                             .attributes()
@@ -86,39 +132,32 @@ fn analyze(
                         },
                     );
 
-                    let d_lon = found_lon - lon;
-                    let d_lat = found_lat - lat;
-                    let x = (d_lon) * deg_lon_to_dist;
-                    let y = (d_lat) * deg_lat_to_dist;
-
-                    let dist = if let Some((last_x, last_y)) = last_point {
-                        let d_x = x - last_x;
-                        let d_y: f64 = y - last_y;
-
-                        let a = d_y.atan2(d_x) * -1.0;
-
-                        let dist = (-x * a.sin() + y * a.cos()).abs();
-
-                        let last_t_x = last_x * a.cos() + last_y * a.sin();
-
-                        let t_x = x * a.cos() + y * a.sin();
-
-                        if (last_t_x >= 0.0 && t_x <= 0.0) || (last_t_x <= 0.0 && t_x >= 0.0) {
-                            dist
-                        } else {
-                            f64::hypot(x, y)
-                        }
+                    let found_lat_rad = found_lat.to_radians();
+                    let found_lon_rad = found_lon.to_radians();
+
+                    let dist = if let Some((last_lat, last_lon)) = last_point {
+                        geo::distance_to_segment(
+                            last_lat,
+                            last_lon,
+                            found_lat_rad,
+                            found_lon_rad,
+                            lat,
+                            lon,
+                        )
                     } else {
-                        f64::hypot(x, y)
+                        geo::haversine_distance(lat, lon, found_lat_rad, found_lon_rad)
                     };
 
                     if (nearest.is_none() || nearest.as_ref().unwrap().distance > dist)
                         && dist > distance
                     {
+                        previous_nearest = nearest.take();
                         nearest = Some(GpxResult {
                             distance: dist,
                             path: path.to_str().unwrap().to_string(),
                             time: None,
+                            lat: found_lat,
+                            lon: found_lon,
                         });
                         time_update = true;
                     }
@@ -136,17 +175,31 @@ fn analyze(
                             distance: dist,
                             path: path.to_str().unwrap().to_string(),
                             time: None,
+                            lat: found_lat,
+                            lon: found_lon,
                         });
                         searching_time_for = new_results.len() - 1;
                     }
-                    last_point = Some((x, y));
+                    last_point = Some((found_lat_rad, found_lon_rad));
                 } else if e.name().eq(b"time") {
                     in_time = true;
                 }
             }
             Ok(quick_xml::events::Event::End(e)) => {
                 if e.name().eq(b"trkpt") {
+                    // A window filter is active but this point never carried
+                    // a usable timestamp, so we can't confirm it belongs in
+                    // the window; drop whatever it tentatively contributed.
+                    if (after.is_some() || before.is_some()) && !saw_time_this_point {
+                        if searching_time_for != std::usize::MAX {
+                            new_results.pop();
+                        } else if time_update {
+                            nearest = previous_nearest.take();
+                        }
+                    }
+
                     searching_time_for = std::usize::MAX;
+                    time_update = false;
                 }
                 if e.name().eq(b"time") {
                     in_time = false;
@@ -156,10 +209,27 @@ fn analyze(
                 if in_time {
                     let time = e.unescape_and_decode(&reader).unwrap();
                     if !time.eq("") {
-                        if let Some(time_for) = new_results.get_mut(searching_time_for) {
-                            time_for.time = Some(time);
+                        let in_window = if after.is_none() && before.is_none() {
+                            true
+                        } else {
+                            time.parse::<DateTime<Utc>>().map_or(false, |parsed| {
+                                after.map_or(true, |after| parsed >= after)
+                                    && before.map_or(true, |before| parsed <= before)
+                            })
+                        };
+                        saw_time_this_point = true;
+
+                        if in_window {
+                            if let Some(time_for) = new_results.get_mut(searching_time_for) {
+                                time_for.time = Some(time);
+                            } else if time_update {
+                                nearest.as_mut().unwrap().time = Some(time);
+                            }
+                        } else if searching_time_for != std::usize::MAX {
+                            new_results.pop();
+                            searching_time_for = std::usize::MAX;
                         } else if time_update {
-                            nearest.as_mut().unwrap().time = Some(time);
+                            nearest = previous_nearest.take();
                         }
                     }
                     time_update = false;
@@ -196,8 +266,8 @@ fn read_dir_db(path: impl AsRef<Path>, analyze_db: &mut Vec<PathBuf>, recursive:
             let dir_entry = dir_entry.unwrap();
             if recursive && dir_entry.metadata().unwrap().is_dir() {
                 read_dir_db(dir_entry.path(), analyze_db, recursive);
-            } else if let Some(ext) = dir_entry.path().extension() {
-                if ext.eq("gpx") {
+            } else if let Some(file_name) = dir_entry.path().file_name().and_then(|n| n.to_str()) {
+                if file_name.ends_with(".gpx") || file_name.ends_with(".gpx.gz") {
                     analyze_db.push(dir_entry.path());
                 }
             }
@@ -205,28 +275,7 @@ fn read_dir_db(path: impl AsRef<Path>, analyze_db: &mut Vec<PathBuf>, recursive:
     }
 }
 
-fn parse_deg_min_sec(mut input: String) -> f64 {
-    input = input.trim().to_string();
-    let first = input.remove(0);
-    let south_west = first.eq(&'S') || first.eq(&'W');
-    input = input.trim().to_string();
-
-    let split = input.split(' ').map(str::to_string).collect::<Vec<_>>();
-
-    let mut out = split
-        .get(0)
-        .unwrap()
-        .replace("°", "")
-        .parse::<f64>()
-        .unwrap()
-        + split.get(1).unwrap().parse::<f64>().unwrap() / 60.0;
-    if south_west {
-        out *= -1.0;
-    };
-    out
-}
-
-fn print_result(result: &GpxResult) {
+pub(crate) fn print_result(result: &GpxResult) {
     if let Some(time) = result
         .time
         .as_ref()
@@ -245,54 +294,66 @@ fn print_result(result: &GpxResult) {
     }
 }
 
-fn main() {
-    let opt = Opt::parse();
+fn parse_bbox(input: &str) -> Result<(f64, f64, f64, f64), String> {
+    let (min_part, max_part) = input
+        .split_once(' ')
+        .ok_or_else(|| format!("expected `lat_min,lon_min lat_max,lon_max`, got `{}`", input))?;
+
+    let parse_pair = |part: &str| -> Result<(f64, f64), String> {
+        let (lat_str, lon_str) = part
+            .split_once(',')
+            .ok_or_else(|| format!("expected `lat,lon`, got `{}`", part))?;
+        let lat = lat_str
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid latitude `{}`", lat_str))?;
+        let lon = lon_str
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid longitude `{}`", lon_str))?;
+        Ok((lat, lon))
+    };
 
-    let (latitude, longitude) = {
-        if let Some((Ok(latitude), Ok(longitude))) =
-            opt.coordinate
-                .split_once(' ')
-                .map(|(latitude_str, longitude_str)| {
-                    (
-                        latitude_str.replace(",", "").parse::<f64>(),
-                        longitude_str.replace(",", "").parse::<f64>(),
-                    )
-                })
-        {
-            (latitude, longitude)
-        } else {
-            let (first, second) = opt.coordinate.split_at(opt.coordinate.len() / 2);
+    let (lat_min, lon_min) = parse_pair(min_part)?;
+    let (lat_max, lon_max) = parse_pair(max_part)?;
 
-            (
-                parse_deg_min_sec(first.to_string()),
-                parse_deg_min_sec(second.to_string()),
-            )
-        }
-    };
+    if lat_max < lat_min {
+        return Err(format!(
+            "top latitude ({}) is below bottom latitude ({})",
+            lat_max, lat_min
+        ));
+    }
 
-    // WGS-84: https://en.wikipedia.org/wiki/World_Geodetic_System#WGS84
+    Ok((lat_min, lon_min, lat_max, lon_max))
+}
 
-    let deg_lat_to_dist: f64 = 6_378_137.0_f64.to_radians() * longitude.to_radians().cos();
-    let deg_lon_to_dist: f64 = 6_356_752.314_245_18_f64.to_radians() * latitude.to_radians().cos();
+fn print_bbox_result(point: &index::IndexedPoint) {
+    if let Some(time) = point
+        .time
+        .as_ref()
+        .and_then(|time_str| time_str.parse::<DateTime<Utc>>().ok())
+    {
+        let time_in_local_timezone = time.with_timezone(&chrono::offset::Local);
+        println!(
+            "{:.6};{:.6};{};{};{}",
+            point.lat,
+            point.lon,
+            time_in_local_timezone.time().to_string(),
+            time_in_local_timezone.date().to_string(),
+            point.path.to_str().unwrap()
+        );
+    } else {
+        println!(
+            "{:.6};{:.6};;;{}",
+            point.lat,
+            point.lon,
+            point.path.to_str().unwrap()
+        );
+    }
+}
 
-    println!("{}, {}", latitude, longitude);
-    println!(
-        "{} {}° {} {} {}° {}",
-        if latitude.is_sign_negative() {
-            "S"
-        } else {
-            "N"
-        },
-        latitude as u64,
-        latitude % 1.0 * 60.0,
-        if longitude.is_sign_negative() {
-            "W"
-        } else {
-            "E"
-        },
-        longitude.abs() as u64,
-        longitude.abs() % 1.0 * 60.0,
-    );
+fn main() {
+    let opt = Opt::parse();
 
     if let Some(threads) = opt.threads {
         rayon::ThreadPoolBuilder::new()
@@ -322,37 +383,171 @@ fn main() {
     println!("Found {} gpx file(s)", analyze_db.len());
     println!("Searching in `{}`...", path.to_str().unwrap(),);
 
-    let distance = opt.distance;
-    let mut results = analyze_db
-        .par_iter()
-        .flat_map(|gpx_file| {
-            analyze(
-                gpx_file,
-                latitude,
-                longitude,
-                deg_lat_to_dist,
-                deg_lon_to_dist,
-                distance,
-            )
+    if let Some(image_dir) = &opt.geotag {
+        let spatial_index = index::load_or_build(&path, &analyze_db, opt.reindex);
+        let points = geotag::sorted_points(&spatial_index);
+        geotag::run(image_dir, &points, opt.write_exif);
+        return;
+    }
+
+    if let Some(bbox_str) = &opt.bbox {
+        let (lat_min, lon_min, lat_max, lon_max) = match parse_bbox(bbox_str) {
+            Ok(bbox) => bbox,
+            Err(err) => {
+                eprintln!("[Error] Invalid --bbox: {}", err);
+                process::exit(-1);
+            }
+        };
+
+        let spatial_index = index::load_or_build(&path, &analyze_db, opt.reindex);
+        let points = spatial_index.points_in_bbox(lat_min, lon_min, lat_max, lon_max);
+
+        println!(
+            "Found {} point(s) in bbox ({},{} to {},{}):\n\
+            lat;lon;time;date;path",
+            points.len(),
+            lat_min,
+            lon_min,
+            lat_max,
+            lon_max
+        );
+
+        points.into_iter().for_each(print_bbox_result);
+
+        return;
+    }
+
+    let coordinate_str = opt.coordinate.clone().unwrap_or_else(|| {
+        eprintln!("[Error] --coordinate and --distance are required unless --bbox is given");
+        process::exit(-1);
+    });
+    let distance = opt.distance.unwrap_or_else(|| {
+        eprintln!("[Error] --distance is required unless --bbox is given");
+        process::exit(-1);
+    });
+
+    let coordinate::Coordinate {
+        lat: latitude,
+        lon: longitude,
+    } = coordinate_str.parse().unwrap_or_else(|err| {
+        eprintln!("[Error] Invalid --coordinate: {}", err);
+        process::exit(-1);
+    });
+
+    let parse_bound = |flag: &str, value: &Option<String>| {
+        value.as_ref().map(|value| {
+            DateTime::parse_from_rfc3339(value)
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(|err| {
+                    eprintln!("[Error] Invalid {}: {}", flag, err);
+                    process::exit(-1);
+                })
         })
+    };
+
+    let after = parse_bound("--after", &opt.after);
+    let before = parse_bound("--before", &opt.before);
+
+    println!("{}, {}", latitude, longitude);
+    println!(
+        "{} {}° {} {} {}° {}",
+        if latitude.is_sign_negative() {
+            "S"
+        } else {
+            "N"
+        },
+        latitude as u64,
+        latitude % 1.0 * 60.0,
+        if longitude.is_sign_negative() {
+            "W"
+        } else {
+            "E"
+        },
+        longitude.abs() as u64,
+        longitude.abs() % 1.0 * 60.0,
+    );
+
+    let spatial_index = index::load_or_build(&path, &analyze_db, opt.reindex);
+
+    let candidate_files = spatial_index
+        .points_within_distance(latitude, longitude, distance)
+        .into_iter()
+        .map(|point| point.path.clone())
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    // Narrowing to the indexed candidates only pays off when it actually
+    // found something; an empty result still needs the full scan below to
+    // report the globally nearest point.
+    let files_to_scan = if candidate_files.is_empty() {
+        &analyze_db
+    } else {
+        &candidate_files
+    };
+
+    let mut results = files_to_scan
+        .par_iter()
+        .flat_map(|gpx_file| analyze(gpx_file, latitude, longitude, distance, after, before))
         .collect::<Vec<_>>();
 
     results
         .sort_by(|result_1, result_2| result_1.distance.partial_cmp(&result_2.distance).unwrap());
 
-    let distance = opt.distance;
+    if let Some(bin_str) = &opt.bin {
+        let bin = binning::parse_bin_duration(bin_str).unwrap_or_else(|err| {
+            eprintln!("[Error] Invalid --bin: {}", err);
+            process::exit(-1);
+        });
+
+        let results_within_distance = results
+            .iter()
+            .filter(|result| result.distance <= distance)
+            .collect::<Vec<_>>();
+
+        let buckets = binning::bucket_nearest(
+            &results_within_distance,
+            bin,
+            |result| result.time.as_ref().and_then(|t| t.parse::<DateTime<Utc>>().ok()),
+            |result| result.distance,
+        );
+
+        println!(
+            "Found {} bucket(s) with a closest approach within distance ({}m):\n\
+            bucket;dist;time;date;path",
+            buckets.len(),
+            distance
+        );
+
+        for (bucket_start, index) in &buckets {
+            print!("{};", bucket_start.to_rfc3339());
+            print_result(results_within_distance[*index]);
+        }
+
+        return;
+    }
+
+    let output_format = opt.format.parse::<format::OutputFormat>().unwrap_or_else(|err| {
+        eprintln!("[Error] Invalid --format: {}", err);
+        process::exit(-1);
+    });
 
     let results_within_distance = results
         .par_iter()
         .filter(|result| result.distance <= distance)
         .collect::<Vec<_>>();
 
+    if output_format != format::OutputFormat::Text {
+        format::print_results(output_format, &results_within_distance);
+        return;
+    }
+
     if !results_within_distance.is_empty() {
         println!(
             "Found {} point(s) within distance ({}m):\n\
             dist;time;date;path",
             results_within_distance.len(),
-            opt.distance
+            distance
         );
 
         let out_range_index = results_within_distance.len();
@@ -374,3 +569,27 @@ fn main() {
         println!("Did not find any points.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bbox_accepts_valid_rectangle() {
+        assert_eq!(
+            parse_bbox("48.1,16.2 48.3,16.4").unwrap(),
+            (48.1, 16.2, 48.3, 16.4)
+        );
+    }
+
+    #[test]
+    fn parse_bbox_rejects_swapped_latitudes() {
+        let err = parse_bbox("48.3,16.2 48.1,16.4").unwrap_err();
+        assert!(err.contains("top latitude"));
+    }
+
+    #[test]
+    fn parse_bbox_rejects_malformed_input() {
+        assert!(parse_bbox("48.1 16.2").is_err());
+    }
+}